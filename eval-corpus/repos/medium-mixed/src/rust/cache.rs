@@ -1,30 +1,134 @@
-use std::collections::HashMap;
+use hashlink::LinkedHashMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, SystemTime};
 
+const DEFAULT_SHARDS: usize = 16;
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// A TTL as it would come from a settings file: either a `{days, hours,
+/// minutes}` structure, or a bare bool (`false` disables caching entirely,
+/// `true` means "use the default TTL"). Lets callers configure caching
+/// without hand-computing a `Duration`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TtlSetting {
+    Structured { days: u64, hours: u64, minutes: u64 },
+    Enabled(bool),
+}
+
+impl TtlSetting {
+    /// Resolves the setting to a concrete TTL, or `None` if caching is
+    /// disabled for this entry.
+    pub fn into_duration(self) -> Option<Duration> {
+        match self {
+            TtlSetting::Enabled(false) => None,
+            TtlSetting::Enabled(true) => Some(DEFAULT_TTL),
+            TtlSetting::Structured { days, hours, minutes } => {
+                Some(Duration::from_secs(days * 86_400 + hours * 3_600 + minutes * 60))
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
-pub struct CacheEntry {
-    pub value: String,
+pub struct CacheEntry<V> {
+    pub value: V,
     pub expires_at: SystemTime,
+    pub ttl: Duration,
+    pub sliding: bool,
 }
 
-pub struct TtlCache {
-    store: HashMap<String, CacheEntry>,
+pub struct TtlCache<K, V> {
+    store: LinkedHashMap<K, CacheEntry<V>>,
+    capacity: usize,
+    persist_dir: Option<PathBuf>,
 }
 
-impl TtlCache {
+impl<K, V> TtlCache<K, V>
+where
+    K: Hash + Eq,
+    V: Clone,
+{
     pub fn new() -> Self {
-        Self { store: HashMap::new() }
+        Self { store: LinkedHashMap::new(), capacity: usize::MAX, persist_dir: None }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self { store: LinkedHashMap::new(), capacity: cap, persist_dir: None }
+    }
+
+    pub fn insert(&mut self, key: K, value: V, ttl: Duration) {
+        self.insert_entry(key, value, ttl, false);
+    }
+
+    /// Like [`insert`](Self::insert), but reading the entry extends its
+    /// lifetime by `ttl` instead of leaving a fixed expiry in place -
+    /// useful for session-like data that should stay alive while active.
+    pub fn insert_sliding(&mut self, key: K, value: V, ttl: Duration) {
+        self.insert_entry(key, value, ttl, true);
     }
 
-    pub fn insert(&mut self, key: String, value: String, ttl: Duration) {
-        let expires_at = SystemTime::now() + ttl;
-        self.store.insert(key, CacheEntry { value, expires_at });
+    fn insert_entry(&mut self, key: K, value: V, ttl: Duration, sliding: bool) {
+        self.insert_entry_at(key, value, SystemTime::now() + ttl, ttl, sliding);
     }
 
-    pub fn get(&mut self, key: &str) -> Option<String> {
-        if let Some(entry) = self.store.get(key) {
+    /// Like [`insert_entry`](Self::insert_entry), but with an explicit
+    /// `expires_at` instead of deriving it from `ttl` - used when
+    /// reinstating a persisted entry, where `expires_at` reflects time
+    /// remaining since the entry was written to disk while `ttl` is the
+    /// original window (needed to re-arm sliding entries correctly).
+    fn insert_entry_at(&mut self, key: K, value: V, expires_at: SystemTime, ttl: Duration, sliding: bool) {
+        self.store.insert(key, CacheEntry { value, expires_at, ttl, sliding });
+        if self.store.len() > self.capacity {
+            self.store.pop_front();
+        }
+    }
+
+    /// Insert using a settings-file-style [`TtlSetting`] instead of a raw
+    /// `Duration`. Returns `false` without inserting if the setting
+    /// disables caching for this entry.
+    pub fn insert_with_setting(&mut self, key: K, value: V, setting: TtlSetting) -> bool {
+        match setting.into_duration() {
+            Some(ttl) => {
+                self.insert(key, value, ttl);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_with_expiry(key).map(|(value, _)| value)
+    }
+
+    /// Like [`get`](Self::get), but also returns the entry's expiry instant
+    /// so callers can set HTTP `Cache-Control`/`Age` headers without a
+    /// second lookup.
+    pub fn get_with_expiry<Q>(&mut self, key: &Q) -> Option<(V, SystemTime)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(entry) = self.store.get_mut(key) {
             if SystemTime::now() <= entry.expires_at {
-                return Some(entry.value.clone());
+                if entry.sliding {
+                    entry.expires_at = SystemTime::now() + entry.ttl;
+                }
+                let value = entry.value.clone();
+                let expires_at = entry.expires_at;
+                self.store.to_back(key);
+                return Some((value, expires_at));
             }
         }
         self.store.remove(key);
@@ -37,3 +141,340 @@ impl TtlCache {
         before - self.store.len()
     }
 }
+
+#[derive(Serialize, Deserialize)]
+struct PersistedEntry<K, V> {
+    key: K,
+    value: V,
+    /// Time remaining until expiry as of the moment this was written -
+    /// mirrored against the file's mtime on read to derive the current
+    /// remaining TTL rather than handing back a fresh full window.
+    ttl_millis: u64,
+    /// The entry's original configured TTL window, independent of how much
+    /// of it remained at write time. Needed to re-arm a sliding entry's
+    /// `ttl` field on reload so later accesses extend it by the right
+    /// amount instead of by whatever happened to remain at load time.
+    original_ttl_millis: u64,
+    sliding: bool,
+}
+
+/// Disk-backed persistence for [`TtlCache`], so entries survive process
+/// restarts. Validity on load is judged by the persisted file's mtime
+/// rather than a clock stored in the cache, since the process may have
+/// been down for an arbitrary stretch of the TTL.
+impl<K, V> TtlCache<K, V>
+where
+    K: Hash + Eq + Clone + Serialize + DeserializeOwned,
+    V: Clone + Serialize + DeserializeOwned,
+{
+    pub fn persistent(dir: PathBuf) -> Self {
+        Self { store: LinkedHashMap::new(), capacity: usize::MAX, persist_dir: Some(dir) }
+    }
+
+    /// Combines capacity-bounded LRU eviction with disk-backed persistence -
+    /// [`with_capacity`](Self::with_capacity) and [`persistent`](Self::persistent)
+    /// each hardcode the other knob, so there was previously no way to get both.
+    pub fn with_capacity_and_dir(cap: usize, dir: PathBuf) -> Self {
+        Self { store: LinkedHashMap::new(), capacity: cap, persist_dir: Some(dir) }
+    }
+
+    fn path_for(dir: &Path, key: &K) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    fn write_entry(dir: &Path, key: &K, value: &V, ttl: Duration, original_ttl: Duration, sliding: bool) {
+        let _ = fs::create_dir_all(dir);
+        let entry = PersistedEntry {
+            key: key.clone(),
+            value: value.clone(),
+            ttl_millis: ttl.as_millis() as u64,
+            original_ttl_millis: original_ttl.as_millis() as u64,
+            sliding,
+        };
+        if let Ok(bytes) = serde_json::to_vec(&entry) {
+            let _ = fs::write(Self::path_for(dir, key), bytes);
+        }
+    }
+
+    /// `Some(remaining)` if the file's mtime is within `ttl` of now, where
+    /// `remaining` is what's left of the original `ttl` window - so a
+    /// reinstated entry expires when the persisted one would have, not
+    /// `ttl` further out. Clock-skew failures reading `modified()`/
+    /// `elapsed()` are logged and treated as a fresh full `ttl` rather than
+    /// panicking or evicting a possibly-fresh entry.
+    fn remaining_ttl(metadata: &fs::Metadata, ttl: Duration) -> Option<Duration> {
+        match metadata.modified().map(|modified| modified.elapsed()) {
+            Ok(Ok(elapsed)) if elapsed <= ttl => Some(ttl.saturating_sub(elapsed)),
+            Ok(Ok(_)) => None,
+            _ => {
+                log::warn!("cache entry mtime is unreadable (clock skew?); treating as valid");
+                Some(ttl)
+            }
+        }
+    }
+
+    fn read_entry(dir: &Path, key: &K) -> Option<(V, Duration, Duration, bool)> {
+        let path = Self::path_for(dir, key);
+        let metadata = fs::metadata(&path).ok()?;
+        let bytes = fs::read(&path).ok()?;
+        let entry: PersistedEntry<K, V> = serde_json::from_slice(&bytes).ok()?;
+        if entry.key != *key {
+            return None;
+        }
+        let ttl = Duration::from_millis(entry.ttl_millis);
+        let original_ttl = Duration::from_millis(entry.original_ttl_millis);
+        let remaining = Self::remaining_ttl(&metadata, ttl)?;
+        Some((entry.value, remaining, original_ttl, entry.sliding))
+    }
+
+    /// Insert into the in-memory store and persist the entry to disk, if a
+    /// persistence directory is configured.
+    pub fn insert_persistent(&mut self, key: K, value: V, ttl: Duration) {
+        if let Some(dir) = self.persist_dir.clone() {
+            Self::write_entry(&dir, &key, &value, ttl, ttl, false);
+        }
+        self.insert(key, value, ttl);
+    }
+
+    /// Like [`insert_persistent`](Self::insert_persistent), but the
+    /// persisted entry is reinstated as a sliding entry on reload - see
+    /// [`insert_sliding`](Self::insert_sliding).
+    pub fn insert_persistent_sliding(&mut self, key: K, value: V, ttl: Duration) {
+        if let Some(dir) = self.persist_dir.clone() {
+            Self::write_entry(&dir, &key, &value, ttl, ttl, true);
+        }
+        self.insert_sliding(key, value, ttl);
+    }
+
+    /// Look up a key, falling back to the on-disk copy on a cache miss.
+    pub fn get_persistent(&mut self, key: &K) -> Option<V> {
+        if let Some(value) = self.get(key) {
+            return Some(value);
+        }
+        let dir = self.persist_dir.clone()?;
+        let (value, remaining, original_ttl, sliding) = Self::read_entry(&dir, key)?;
+        self.insert_entry_at(key.clone(), value.clone(), SystemTime::now() + remaining, original_ttl, sliding);
+        Some(value)
+    }
+
+    /// Write every in-memory entry out to the persistence directory.
+    pub fn flush(&self) {
+        let Some(dir) = self.persist_dir.clone() else { return };
+        for (key, entry) in self.store.iter() {
+            if let Ok(remaining) = entry.expires_at.duration_since(SystemTime::now()) {
+                Self::write_entry(&dir, key, &entry.value, remaining, entry.ttl, entry.sliding);
+            }
+        }
+    }
+
+    /// Read every still-valid entry from the persistence directory back
+    /// into the in-memory store.
+    pub fn load(&mut self) {
+        let Some(dir) = self.persist_dir.clone() else { return };
+        let Ok(read_dir) = fs::read_dir(&dir) else { return };
+        for file in read_dir.flatten() {
+            let Ok(metadata) = file.metadata() else { continue };
+            let Ok(bytes) = fs::read(file.path()) else { continue };
+            let Ok(entry) = serde_json::from_slice::<PersistedEntry<K, V>>(&bytes) else { continue };
+            let ttl = Duration::from_millis(entry.ttl_millis);
+            let original_ttl = Duration::from_millis(entry.original_ttl_millis);
+            if let Some(remaining) = Self::remaining_ttl(&metadata, ttl) {
+                self.insert_entry_at(entry.key, entry.value, SystemTime::now() + remaining, original_ttl, entry.sliding);
+            }
+        }
+    }
+}
+
+/// Thread-safe wrapper around [`TtlCache`] that shards the keyspace across
+/// `N` independently-locked maps so concurrent access to different keys
+/// doesn't serialize on a single mutex.
+pub struct SyncTtlCache<K, V> {
+    shards: Arc<Vec<Mutex<TtlCache<K, V>>>>,
+}
+
+impl<K, V> SyncTtlCache<K, V>
+where
+    K: Hash + Eq,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    pub fn with_shards(shard_count: usize) -> Self {
+        let shard_count = shard_count.max(1);
+        let shards = (0..shard_count).map(|_| Mutex::new(TtlCache::new())).collect();
+        Self { shards: Arc::new(shards) }
+    }
+
+    fn shard_for<Q>(&self, key: &Q) -> &Mutex<TtlCache<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let index = (hasher.finish() as usize) % self.shards.len();
+        &self.shards[index]
+    }
+
+    /// Locks a shard, recovering the guard if a prior holder panicked while
+    /// it was locked instead of poisoning every future access to that
+    /// shard - a panicking request handler shouldn't take down the cache
+    /// for every other key that happens to hash to the same shard.
+    fn lock_shard(shard: &Mutex<TtlCache<K, V>>) -> MutexGuard<'_, TtlCache<K, V>> {
+        shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Self::lock_shard(self.shard_for(key)).get(key)
+    }
+
+    pub fn insert(&self, key: K, value: V, ttl: Duration) {
+        Self::lock_shard(self.shard_for(&key)).insert(key, value, ttl);
+    }
+
+    pub fn purge_expired(&self) -> usize {
+        self.shards.iter().map(|shard| Self::lock_shard(shard).purge_expired()).sum()
+    }
+}
+
+impl<K, V> Clone for SyncTtlCache<K, V> {
+    fn clone(&self) -> Self {
+        Self { shards: Arc::clone(&self.shards) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn capacity_evicts_least_recently_used() {
+        let mut cache: TtlCache<&str, i32> = TtlCache::with_capacity(2);
+        cache.insert("a", 1, Duration::from_secs(60));
+        cache.insert("b", 2, Duration::from_secs(60));
+        cache.insert("c", 3, Duration::from_secs(60));
+
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(2));
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn string_keyed_cache_supports_borrowed_str_lookups() {
+        let mut cache: TtlCache<String, i32> = TtlCache::new();
+        cache.insert("k".to_string(), 7, Duration::from_secs(60));
+
+        // The core ergonomic promise of generalizing over K: looking up a
+        // `String`-keyed cache with a borrowed `&str`, no .to_string() needed.
+        assert_eq!(cache.get("k"), Some(7));
+    }
+
+    #[test]
+    fn capacity_eviction_respects_access_order_not_just_insertion_order() {
+        let mut cache: TtlCache<&str, i32> = TtlCache::with_capacity(2);
+        cache.insert("a", 1, Duration::from_secs(60));
+        cache.insert("b", 2, Duration::from_secs(60));
+        // Touch "a" so "b" becomes the least recently used entry.
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c", 3, Duration::from_secs(60));
+
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn concurrent_access_across_shards_is_consistent() {
+        let cache: Arc<SyncTtlCache<u32, u32>> = Arc::new(SyncTtlCache::with_shards(4));
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let cache = Arc::clone(&cache);
+                thread::spawn(move || {
+                    for i in 0..50 {
+                        let key = t * 50 + i;
+                        cache.insert(key, key, Duration::from_secs(60));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for key in 0..400 {
+            assert_eq!(cache.get(&key), Some(key));
+        }
+    }
+
+    #[test]
+    fn sliding_entries_refresh_on_access_fixed_entries_do_not() {
+        let mut cache: TtlCache<&str, i32> = TtlCache::new();
+        cache.insert("fixed", 1, Duration::from_millis(60));
+        cache.insert_sliding("sliding", 2, Duration::from_millis(60));
+
+        thread::sleep(Duration::from_millis(40));
+        // Both still within their original 60ms window.
+        assert_eq!(cache.get("sliding"), Some(2)); // resets sliding's expiry to now + 60ms
+        assert_eq!(cache.get("fixed"), Some(1));
+
+        thread::sleep(Duration::from_millis(40));
+        // 80ms since "fixed" was inserted: past its fixed TTL.
+        assert_eq!(cache.get("fixed"), None);
+        // Only 40ms since "sliding" was last read: still alive.
+        assert_eq!(cache.get("sliding"), Some(2));
+    }
+
+    #[test]
+    fn ttl_setting_into_duration_covers_all_shapes() {
+        assert_eq!(TtlSetting::Enabled(false).into_duration(), None);
+        assert_eq!(TtlSetting::Enabled(true).into_duration(), Some(DEFAULT_TTL));
+        assert_eq!(
+            TtlSetting::Structured { days: 1, hours: 2, minutes: 3 }.into_duration(),
+            Some(Duration::from_secs(86_400 + 2 * 3_600 + 3 * 60))
+        );
+    }
+
+    #[test]
+    fn get_with_expiry_returns_value_and_expiry_instant() {
+        let mut cache: TtlCache<&str, i32> = TtlCache::new();
+        cache.insert("k", 42, Duration::from_secs(60));
+
+        let (value, expires_at) = cache.get_with_expiry("k").unwrap();
+        assert_eq!(value, 42);
+        assert!(expires_at > SystemTime::now());
+    }
+
+    #[test]
+    fn persistent_reload_carries_remaining_ttl_not_a_fresh_window() {
+        let dir = std::env::temp_dir()
+            .join(format!("ttl_cache_persistent_round_trip_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let ttl = Duration::from_millis(200);
+        let mut cache: TtlCache<String, String> = TtlCache::persistent(dir.clone());
+        cache.insert_persistent("session".to_string(), "value".to_string(), ttl);
+
+        thread::sleep(Duration::from_millis(80));
+        drop(cache);
+
+        let mut reloaded: TtlCache<String, String> = TtlCache::persistent(dir.clone());
+        reloaded.load();
+
+        let (value, expires_at) = reloaded.get_with_expiry("session").unwrap();
+        assert_eq!(value, "value");
+        let remaining = expires_at.duration_since(SystemTime::now()).unwrap();
+        // ~120ms should remain of the original 200ms window, not a fresh 200ms.
+        assert!(remaining < Duration::from_millis(170), "remaining = {remaining:?}");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}